@@ -0,0 +1,160 @@
+use crate::types::{Price, Quantity, Timestamp, Trade};
+use std::collections::VecDeque;
+
+/// Aggregation bucket width for OHLCV candles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CandleInterval {
+    OneSecond,
+    OneMinute,
+    OneHour,
+}
+
+impl CandleInterval {
+    fn bucket_seconds(self) -> i64 {
+        match self {
+            CandleInterval::OneSecond => 1,
+            CandleInterval::OneMinute => 60,
+            CandleInterval::OneHour => 3600,
+        }
+    }
+}
+
+/// One fixed-interval open/high/low/close/volume bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candle {
+    pub bucket_start: Timestamp,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: Quantity,
+}
+
+impl Candle {
+    fn opening(bucket_start: Timestamp, price: Price, quantity: Quantity) -> Self {
+        Self {
+            bucket_start,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: quantity,
+        }
+    }
+}
+
+/// Rolls a stream of `Trade`s into fixed-interval OHLCV bars, bucketed by `timestamp / interval`.
+/// Keeps the currently-forming candle plus a ring buffer of the last `capacity` closed ones, so
+/// callers (charts, REST/WS handlers) can poll both without re-deriving them from raw trades.
+pub struct CandleAggregator {
+    interval: CandleInterval,
+    capacity: usize,
+    partial: Option<Candle>,
+    closed: VecDeque<Candle>,
+}
+
+impl CandleAggregator {
+    pub fn new(interval: CandleInterval, capacity: usize) -> Self {
+        Self {
+            interval,
+            capacity,
+            partial: None,
+            closed: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn bucket_start(&self, timestamp: Timestamp) -> Timestamp {
+        let bucket_seconds = self.interval.bucket_seconds();
+        let bucket = timestamp.timestamp().div_euclid(bucket_seconds) * bucket_seconds;
+        chrono::DateTime::from_timestamp(bucket, 0).unwrap_or(timestamp)
+    }
+
+    /// Fold one trade into the aggregator. If `trade` lands in a later bucket than the
+    /// in-progress candle, the in-progress candle is closed into the ring buffer first and a
+    /// new one is opened.
+    pub fn push_trade(&mut self, trade: &Trade) {
+        let bucket_start = self.bucket_start(trade.timestamp);
+
+        match self.partial {
+            Some(ref mut candle) if candle.bucket_start == bucket_start => {
+                candle.high = candle.high.max(trade.price);
+                candle.low = candle.low.min(trade.price);
+                candle.close = trade.price;
+                candle.volume += trade.quantity;
+            }
+            Some(candle) => {
+                self.archive(candle);
+                self.partial = Some(Candle::opening(bucket_start, trade.price, trade.quantity));
+            }
+            None => {
+                self.partial = Some(Candle::opening(bucket_start, trade.price, trade.quantity));
+            }
+        }
+    }
+
+    fn archive(&mut self, candle: Candle) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.closed.len() == self.capacity {
+            self.closed.pop_front();
+        }
+        self.closed.push_back(candle);
+    }
+
+    /// The candle currently being built, if any trade has landed in its bucket yet.
+    pub fn current_partial(&self) -> Option<Candle> {
+        self.partial
+    }
+
+    /// The last closed candles, oldest first.
+    pub fn closed_candles(&self) -> impl Iterator<Item = &Candle> {
+        self.closed.iter()
+    }
+
+    pub fn last_closed(&self) -> Option<&Candle> {
+        self.closed.back()
+    }
+
+    /// Seed closed history from Binance's REST klines endpoint, so a chart backed by this
+    /// aggregator starts populated instead of empty. `binance_interval` is Binance's own
+    /// interval string (e.g. `"1m"`) and should match `self.interval`.
+    pub async fn backfill_from_binance(
+        &mut self,
+        symbol: &str,
+        binance_interval: &str,
+        limit: u32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let url = format!(
+            "https://api.binance.com/api/v3/klines?symbol={}&interval={}&limit={}",
+            symbol, binance_interval, limit
+        );
+        let client = reqwest::Client::new();
+        let response = client.get(&url).send().await?;
+        let rows: Vec<serde_json::Value> = response.json().await?;
+
+        for row in &rows {
+            let open_time_ms = row[0].as_i64().ok_or("kline row missing open time")?;
+            let bucket_start = chrono::DateTime::from_timestamp_millis(open_time_ms).ok_or("invalid open time")?;
+
+            let candle = Candle {
+                bucket_start,
+                open: parse_kline_price(&row[1])?,
+                high: parse_kline_price(&row[2])?,
+                low: parse_kline_price(&row[3])?,
+                close: parse_kline_price(&row[4])?,
+                volume: parse_kline_price(&row[5])?,
+            };
+            self.archive(candle);
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a klines row's price/volume string field into fixed-point micros, matching the
+/// convention used throughout this crate's `Price`/`Quantity` types.
+fn parse_kline_price(value: &serde_json::Value) -> Result<Price, Box<dyn std::error::Error>> {
+    let raw = value.as_str().ok_or("expected a string price field")?;
+    Ok((raw.parse::<f64>()? * 1_000_000.0) as u64)
+}
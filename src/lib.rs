@@ -2,11 +2,13 @@ pub mod types;
 pub mod orderbook;
 pub mod matching;
 pub mod market_data;
+pub mod candles;
 
 pub use types::*;
 pub use orderbook::OrderBook;
 pub use matching::*;
 pub use market_data::*;
+pub use candles::*;
 
 #[cfg(test)]
 mod tests;
@@ -6,17 +6,26 @@ use clap::Parser;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Use real market data from Binance instead of demo data
+    /// Use real market data instead of demo data
     #[arg(long)]
     real: bool,
-    
-    /// Trading symbol for real market data (default: BTCUSDT)
+
+    /// Exchange to pull real market data from (binance, kraken, poloniex)
+    #[arg(long, default_value = "binance")]
+    exchange: String,
+
+    /// Trading symbol/pair for real market data (default: BTCUSDT; use the exchange's own
+    /// notation, e.g. XBTUSD for kraken, BTC_USDT for poloniex)
     #[arg(long, default_value = "BTCUSDT")]
     symbol: String,
     
     /// Update interval in milliseconds for real market data (default: 1000)
     #[arg(long, default_value = "1000")]
     interval: u64,
+
+    /// Use the incremental WebSocket diff-depth stream instead of REST polling (requires --real)
+    #[arg(long)]
+    diff_stream: bool,
 }
 
 #[tokio::main]
@@ -101,16 +110,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Check if user wants real market data
     if args.real {
-        println!("\nStarting live market data feed from Binance...");
+        println!("\nStarting live market data feed from {}...", args.exchange);
         println!("Symbol: {}", args.symbol);
-        println!("Update interval: {}ms", args.interval);
         println!("Press Ctrl+C to stop\n");
-        
-        let feed = BinanceMarketDataFeed::new(args.symbol);
-        feed.start_live_feed(order_book, args.interval).await?;
+
+        if args.diff_stream {
+            if args.exchange != "binance" {
+                return Err(format!("--diff-stream is only supported for binance, not {}", args.exchange).into());
+            }
+            println!("Mode: incremental diff-depth WebSocket stream");
+            let feed = BinanceMarketDataFeed::new(args.symbol);
+            feed.start_diff_stream(order_book).await.map_err(|e| e as Box<dyn std::error::Error>)?;
+        } else {
+            println!("Mode: REST polling every {}ms", args.interval);
+            let feed: Box<dyn MarketDataFeed> = match args.exchange.as_str() {
+                "binance" => Box::new(BinanceMarketDataFeed::new(args.symbol)),
+                "kraken" => Box::new(KrakenMarketDataFeed::new(args.symbol)),
+                "poloniex" => Box::new(PoloniexMarketDataFeed::new(args.symbol)),
+                other => return Err(format!("unknown exchange: {}", other).into()),
+            };
+            feed.start_live_feed(order_book, args.interval).await.map_err(|e| e as Box<dyn std::error::Error>)?;
+        }
     } else {
         println!("\nTo use real market data, run with: cargo run -- --real");
-        println!("Example: cargo run -- --real --symbol ETHUSDT --interval 2000");
+        println!("Example: cargo run -- --real --exchange binance --symbol ETHUSDT --interval 2000");
+        println!("Example (gap-free WebSocket feed): cargo run -- --real --exchange binance --symbol ETHUSDT --diff-stream");
+        println!("Other exchanges: cargo run -- --real --exchange kraken --symbol XBTUSD");
     }
     
     Ok(())
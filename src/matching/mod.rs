@@ -17,6 +17,19 @@ impl MatchingEngine {
         order_book.add_order(order)
     }
 
+    /// Register per-symbol tick/lot/min-size constraints, creating the order book for `symbol`
+    /// if it doesn't exist yet.
+    pub fn set_market_config(&mut self, symbol: &str, config: MarketConfig) {
+        self.order_books
+            .entry(symbol.to_string())
+            .or_insert_with(crate::orderbook::OrderBook::new)
+            .set_market_config(config);
+    }
+
+    pub fn get_market_config(&self, symbol: &str) -> Option<&MarketConfig> {
+        self.order_books.get(symbol).and_then(|ob| ob.get_market_config())
+    }
+
     pub fn cancel_order(&mut self, symbol: &str, order_id: OrderId) -> Result<(), OrderBookError> {
         if let Some(order_book) = self.order_books.get_mut(symbol) {
             order_book.cancel_order(order_id)
@@ -33,6 +46,23 @@ impl MatchingEngine {
         }
     }
 
+    /// Cancel every resting order on `symbol` belonging to `user_id` whose `client_order_id` is
+    /// in `ids`, in one pass.
+    pub fn cancel_orders_by_client_ids(&mut self, symbol: &str, user_id: &str, ids: &[u64]) -> Vec<OrderId> {
+        self.order_books
+            .get_mut(symbol)
+            .map(|order_book| order_book.cancel_orders_by_client_ids(user_id, ids))
+            .unwrap_or_default()
+    }
+
+    /// Cancel all of a single user's resting orders on `symbol`.
+    pub fn cancel_all_orders(&mut self, symbol: &str, user_id: &str) -> Vec<OrderId> {
+        self.order_books
+            .get_mut(symbol)
+            .map(|order_book| order_book.cancel_all_orders(user_id))
+            .unwrap_or_default()
+    }
+
     pub fn get_order_book_snapshot(&self, symbol: &str, max_levels: usize) -> Option<OrderBookSnapshot> {
         self.order_books.get(symbol).map(|ob| ob.get_order_book_snapshot(max_levels))
     }
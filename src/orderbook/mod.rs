@@ -2,8 +2,38 @@ use crate::types::*;
 use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::Instant;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
+/// Maximum number of expired resting orders evicted while walking a single matching pass.
+/// Bounds worst-case latency; anything beyond this is reaped lazily on later operations.
+const DROP_EXPIRED_ORDER_LIMIT: u32 = 5;
+
+/// Backlog size for the level-delta broadcast channel; a lagging subscriber should just
+/// resync from a fresh checkpoint rather than block publishers.
+const LEVEL_UPDATE_CHANNEL_CAPACITY: usize = 1024;
+
+/// Outcome of attempting to match the incoming order against the resting order at the front of
+/// a price level.
+enum MatchStep {
+    Trade(Trade),
+    /// The front order had expired and was evicted instead of traded against - progress was
+    /// made, so the caller should keep walking this same price level.
+    ExpiredEvicted,
+    /// The front order had expired but the per-pass eviction budget was already exhausted, so
+    /// it was left in place blocking the level instead of being evicted. No progress was
+    /// possible here; the caller should stop matching entirely rather than loop forever against
+    /// the same stale quote.
+    ExpiredBlocked,
+    /// Self-trade prevention fired instead of generating a `Trade`. `consumed` is how much of the
+    /// incoming order's remaining quantity the policy used up; `cancel_incoming` tells the caller
+    /// to discard whatever remainder is left rather than continue matching or resting it.
+    SelfTrade {
+        consumed: Quantity,
+        cancel_incoming: bool,
+    },
+}
+
 #[derive(Debug, Clone)]
 struct OrderEntry {
     order: Arc<Order>,
@@ -68,6 +98,7 @@ impl PriceLevel {
     }
 }
 
+#[derive(Clone)]
 pub struct OrderBook {
     // bids: highest price first (descending order)
     bids: BTreeMap<Price, PriceLevel>,
@@ -75,14 +106,39 @@ pub struct OrderBook {
     asks: BTreeMap<Price, PriceLevel>,
     // order lookup: O(1) access to orders
     orders: HashMap<OrderId, OrderEntry>,
+    // per-user index of resting order ids, for bulk cancellation without scanning the book
+    user_order_index: HashMap<String, std::collections::HashSet<OrderId>>,
+    // (user_id, client_order_id) -> internal OrderId, for cancelling by caller-chosen id
+    client_order_index: HashMap<(String, u64), OrderId>,
     // statistics
     stats: MarketDataStats,
+    // last sequence number accepted from an external feed (see `MarketDataProcessor::
+    // process_market_data`'s gap check); distinct from `book_revision`, which counts this
+    // book's own level-delta broadcasts and has nothing to do with feed sequencing
     pub last_sequence_number: u64,
+    // monotonic counter bumped once per `emit_level_update` call (i.e. once per level-delta,
+    // not once per inbound message); this is the sequence space `LevelUpdate`/`LevelDelta`/
+    // `BookCheckpoint` live in, and what subscribers compare against to detect a gap
+    pub book_revision: u64,
     is_initialized: bool,
     // day reset configuration
     day_reset_hour: u8,
     day_reset_minute: u8,
     last_day_reset: Timestamp,
+    // venue constraints for this symbol (tick/lot/min-size); none means unconstrained
+    market_config: Option<MarketConfig>,
+    // publishes a LevelUpdate whenever a price level's aggregate quantity changes
+    level_update_tx: broadcast::Sender<LevelUpdate>,
+    // reference price `OrderType::OraclePeg` orders compute their effective price against
+    oracle_price: Option<Price>,
+    // dormant stop-buys, keyed ascending by trigger: fire once the market trades at or above it
+    stop_buys: BTreeMap<Price, VecDeque<Arc<Order>>>,
+    // dormant stop-sells, keyed ascending by trigger: fire once the market trades at or below it
+    stop_sells: BTreeMap<Price, VecDeque<Arc<Order>>>,
+    // price of the most recent trade, used to arm `Stop`/`StopLimit` orders
+    last_trade_price: Option<Price>,
+    // level deltas queued since the last `drain_level_deltas` call, for poll-based consumers
+    pending_deltas: Vec<LevelDelta>,
 }
 
 impl OrderBook {
@@ -91,37 +147,208 @@ impl OrderBook {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
             orders: HashMap::new(),
+            user_order_index: HashMap::new(),
+            client_order_index: HashMap::new(),
             stats: MarketDataStats::default(),
             last_sequence_number: 0,
+            book_revision: 0,
             is_initialized: false,
             day_reset_hour: 15,
             day_reset_minute: 59,
             last_day_reset: chrono::Utc::now(),
+            market_config: None,
+            level_update_tx: broadcast::channel(LEVEL_UPDATE_CHANNEL_CAPACITY).0,
+            oracle_price: None,
+            stop_buys: BTreeMap::new(),
+            stop_sells: BTreeMap::new(),
+            last_trade_price: None,
+            pending_deltas: Vec::new(),
+        }
+    }
+
+    /// Subscribe to this book's level-delta stream. Returns a checkpoint the caller should
+    /// apply first, followed by a receiver of `LevelUpdate`s keyed to increasing sequence
+    /// numbers; a gap in those numbers means the subscriber fell behind and must re-subscribe
+    /// for a fresh checkpoint.
+    pub fn subscribe(&self, max_levels: usize) -> (BookCheckpoint, broadcast::Receiver<LevelUpdate>) {
+        (self.checkpoint(max_levels), self.level_update_tx.subscribe())
+    }
+
+    pub fn checkpoint(&self, max_levels: usize) -> BookCheckpoint {
+        let snapshot = self.get_order_book_snapshot(max_levels);
+        BookCheckpoint {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            sequence_number: snapshot.sequence_number,
+            timestamp: snapshot.timestamp,
         }
     }
 
+    /// Bump `book_revision` and publish the current state of `price` on `side` to level-delta
+    /// subscribers. Called after any mutation that changes a level's aggregate.
+    fn emit_level_update(&mut self, side: Side, price: Price) {
+        self.book_revision += 1;
+        let (new_quantity, order_count) = match side {
+            Side::Buy => self.bids.get(&price).map_or((0, 0), |level| (level.total_quantity, level.orders.len())),
+            Side::Sell => self.asks.get(&price).map_or((0, 0), |level| (level.total_quantity, level.orders.len())),
+        };
+        let _ = self.level_update_tx.send(LevelUpdate {
+            side,
+            price,
+            new_quantity,
+            order_count,
+            sequence_number: self.book_revision,
+        });
+        self.pending_deltas.push(LevelDelta {
+            side,
+            price,
+            new_quantity,
+            sequence_number: self.book_revision,
+        });
+    }
+
+    /// Drain and return every `LevelDelta` queued since the last call, in sequence-number order.
+    /// A poll-based consumer bootstraps from `checkpoint()` and then applies these in order,
+    /// detecting gaps via `sequence_number`.
+    pub fn drain_level_deltas(&mut self) -> Vec<LevelDelta> {
+        std::mem::take(&mut self.pending_deltas)
+    }
+
     pub fn set_day_reset_time(&mut self, hour: u8, minute: u8) {
         self.day_reset_hour = hour;
         self.day_reset_minute = minute;
     }
 
-    pub fn add_order(&mut self, order: Order) -> Result<Vec<Trade>, OrderBookError> {
+    /// Register tick/lot/min-size constraints for this symbol; every order passed to
+    /// `add_order` is validated against them before it touches the book.
+    pub fn set_market_config(&mut self, config: MarketConfig) {
+        self.market_config = Some(config);
+    }
+
+    pub fn get_market_config(&self) -> Option<&MarketConfig> {
+        self.market_config.as_ref()
+    }
+
+    fn validate_market_config(&self, order: &Order) -> Result<(), OrderBookError> {
+        let Some(config) = &self.market_config else {
+            return Ok(());
+        };
+
+        if order.order_type != OrderType::Market && order.price % config.tick_size != 0 {
+            return Err(OrderBookError::InvalidTickSize { price: order.price });
+        }
+        if order.quantity % config.lot_size != 0 {
+            return Err(OrderBookError::InvalidLotSize { quantity: order.quantity });
+        }
+        if order.quantity < config.min_size {
+            return Err(OrderBookError::BelowMinimumSize { quantity: order.quantity });
+        }
+
+        Ok(())
+    }
+
+    /// Effective price an `OrderType::OraclePeg` order should trade/rest at right now: the
+    /// current `oracle_price` plus the peg's `offset`, clamped so it never trades through
+    /// `limit`. Non-pegged orders just keep their own `price`. `add_order` rejects peg orders
+    /// outright while `oracle_price` is unset, so the `None` case below is only reachable if a
+    /// caller invokes this directly; it falls back to the order's own `price` rather than panic.
+    fn effective_peg_price(&self, order: &Order) -> Price {
+        let OrderType::OraclePeg { offset, limit } = order.order_type else {
+            return order.price;
+        };
+        let Some(oracle_price) = self.oracle_price else {
+            return order.price;
+        };
+        let raw = (oracle_price as i64 + offset).max(0) as Price;
+        match (order.side, limit) {
+            (Side::Buy, Some(limit)) => raw.min(limit),
+            (Side::Sell, Some(limit)) => raw.max(limit),
+            _ => raw,
+        }
+    }
+
+    /// Re-evaluate every resting `OraclePeg` order against a new reference price, move it to its
+    /// new book level if the effective price changed, and run matching for any that now cross.
+    /// Lets passive market makers quote relative to an index without cancel/replace churn.
+    pub fn set_oracle_price(&mut self, reference: Price) -> Vec<Trade> {
+        self.oracle_price = Some(reference);
+
+        // `self.orders` is a `HashMap`, so its iteration order is arbitrary; sort by
+        // `timestamp` before repricing so orders that land on the same new offset bucket are
+        // re-added (and therefore re-queued within their `PriceLevel`) in the same relative
+        // order they were originally resting in, preserving FIFO time priority.
+        let mut pegged: Vec<Arc<Order>> = self
+            .orders
+            .values()
+            .map(|entry| entry.order.clone())
+            .filter(|order| matches!(order.order_type, OrderType::OraclePeg { .. }))
+            .collect();
+        pegged.sort_by_key(|order| (order.timestamp, order.id));
+
+        let mut trades = Vec::new();
+        for order in pegged {
+            let new_price = self.effective_peg_price(&order);
+            if new_price == order.price {
+                continue;
+            }
+
+            // pull it out of its old level and feed it back through `add_order`, which will
+            // recompute (and land on) the same `new_price` and run matching for it, preserving
+            // its `OrderId` so callers tracking it by id see it unchanged
+            if self.cancel_order(order.id).is_err() {
+                continue; // already gone (e.g. concurrently cancelled)
+            }
+            let mut repriced = order.as_ref().clone();
+            repriced.price = new_price;
+            if let Ok(new_trades) = self.add_order(repriced) {
+                trades.extend(new_trades);
+            }
+        }
+
+        trades
+    }
+
+    pub fn add_order(&mut self, mut order: Order) -> Result<Vec<Trade>, OrderBookError> {
         let start_time = Instant::now();
-        
+
         // check if order already exists
         if self.orders.contains_key(&order.id) {
             return Err(OrderBookError::OrderAlreadyExists { order_id: order.id });
         }
 
-        // validate order
-        if order.price == 0 && order.order_type != OrderType::Market {
+        let is_stop_order = matches!(order.order_type, OrderType::Stop { .. } | OrderType::StopLimit { .. });
+
+        // validate order - stop orders are keyed by `trigger_price`/`limit_price` instead, so
+        // `order.price` doesn't apply to them
+        if order.price == 0
+            && !is_stop_order
+            && !matches!(order.order_type, OrderType::Market | OrderType::OraclePeg { .. })
+        {
             return Err(OrderBookError::InvalidPrice { price: order.price });
         }
-        
+
         if order.quantity == 0 {
             return Err(OrderBookError::InvalidQuantity { quantity: order.quantity });
         }
 
+        if order.is_expired(chrono::Utc::now()) {
+            return Err(OrderBookError::OrderExpired { order_id: order.id });
+        }
+
+        if matches!(order.order_type, OrderType::OraclePeg { .. }) {
+            // a peg order computed against an unknown oracle price would rest at its raw
+            // (often zero) `price`, instantly crossing and sweeping the book the moment
+            // anything else calls into matching — reject until `set_oracle_price` has run
+            if self.oracle_price.is_none() {
+                return Err(OrderBookError::OraclePriceUnknown { order_id: order.id });
+            }
+            order.price = self.effective_peg_price(&order);
+        }
+
+        if !is_stop_order {
+            self.validate_market_config(&order)?;
+        }
+
         let order_arc = Arc::new(order);
         let mut trades = Vec::new();
 
@@ -136,6 +363,12 @@ impl OrderBook {
             OrderType::ImmediateOrCancel => {
                 trades = self.match_immediate_or_cancel(&order_arc)?;
             }
+            OrderType::PostOnly | OrderType::PostOnlySlide => {
+                trades = self.add_post_only(&order_arc)?;
+            }
+            OrderType::Stop { .. } | OrderType::StopLimit { .. } => {
+                self.add_stop_order(&order_arc);
+            }
             _ => {
                 // limit orders, gtc, gfd - add to book and try to match
                 trades = self.add_to_book_and_match(&order_arc)?;
@@ -153,6 +386,12 @@ impl OrderBook {
         }
         self.stats.new_orders += 1;
 
+        // arm/cascade stop orders off the new last trade price before returning
+        if let Some(last_trade) = trades.last() {
+            self.last_trade_price = Some(last_trade.price);
+            trades.extend(self.check_and_trigger_stops());
+        }
+
         Ok(trades)
     }
 
@@ -162,24 +401,56 @@ impl OrderBook {
         let order_entry = self.orders.remove(&order_id)
             .ok_or(OrderBookError::OrderNotFound { order_id })?;
 
-        let order = &order_entry.order;
-        
-        match order.side {
-            Side::Buy => {
-                if let Some(price_level) = self.bids.get_mut(&order.price) {
-                    price_level.remove_order(order_id);
-                    if price_level.orders.is_empty() {
-                        self.bids.remove(&order.price);
+        let side = order_entry.order.side;
+        let price = order_entry.order.price;
+
+        if let Some(user_id) = &order_entry.order.user_id {
+            if let Some(ids) = self.user_order_index.get_mut(user_id) {
+                ids.remove(&order_id);
+                if ids.is_empty() {
+                    self.user_order_index.remove(user_id);
+                }
+            }
+            if let Some(client_order_id) = order_entry.order.client_order_id {
+                self.client_order_index.remove(&(user_id.clone(), client_order_id));
+            }
+        }
+
+        match order_entry.order.order_type {
+            // dormant stops never made it into `bids`/`asks`; pull them out of the trigger
+            // queue they're keyed by instead.
+            OrderType::Stop { trigger_price } | OrderType::StopLimit { trigger_price, .. } => {
+                let stops = match side {
+                    Side::Buy => &mut self.stop_buys,
+                    Side::Sell => &mut self.stop_sells,
+                };
+                if let Some(queue) = stops.get_mut(&trigger_price) {
+                    queue.retain(|o| o.id != order_id);
+                    if queue.is_empty() {
+                        stops.remove(&trigger_price);
                     }
                 }
             }
-            Side::Sell => {
-                if let Some(price_level) = self.asks.get_mut(&order.price) {
-                    price_level.remove_order(order_id);
-                    if price_level.orders.is_empty() {
-                        self.asks.remove(&order.price);
+            _ => {
+                match side {
+                    Side::Buy => {
+                        if let Some(price_level) = self.bids.get_mut(&price) {
+                            price_level.remove_order(order_id);
+                            if price_level.orders.is_empty() {
+                                self.bids.remove(&price);
+                            }
+                        }
+                    }
+                    Side::Sell => {
+                        if let Some(price_level) = self.asks.get_mut(&price) {
+                            price_level.remove_order(order_id);
+                            if price_level.orders.is_empty() {
+                                self.asks.remove(&price);
+                            }
+                        }
                     }
                 }
+                self.emit_level_update(side, price);
             }
         }
 
@@ -199,10 +470,7 @@ impl OrderBook {
             .clone();
 
         let old_order = order_entry.order.clone();
-        
-        // cancel the old order
-        self.cancel_order(order_id)?;
-        
+
         // create new order with modifications
         let mut new_order = (*old_order).clone();
         if let Some(price) = new_price {
@@ -211,6 +479,19 @@ impl OrderBook {
         if let Some(quantity) = new_quantity {
             new_order.remaining_quantity = quantity;
         }
+
+        // validate against market config before touching the book, so a rejected modification
+        // (e.g. a dust size or an off-tick price) leaves the original order resting untouched;
+        // the new size lives only in `remaining_quantity`, so check that in place of `quantity`
+        let mut candidate = new_order.clone();
+        if let Some(quantity) = new_quantity {
+            candidate.quantity = quantity;
+        }
+        self.validate_market_config(&candidate)?;
+
+        // cancel the old order
+        self.cancel_order(order_id)?;
+
         new_order.id = Uuid::new_v4(); // new id for the modified order
         new_order.timestamp = chrono::Utc::now();
 
@@ -247,7 +528,10 @@ impl OrderBook {
             bids,
             asks,
             timestamp: chrono::Utc::now(),
-            sequence_number: self.last_sequence_number,
+            // `book_revision`, not `last_sequence_number`: this is what `checkpoint()` hands
+            // subscribers alongside the `LevelUpdate` stream, and the two need to share a
+            // sequence space for gap detection to work (see `subscribe`)
+            sequence_number: self.book_revision,
         }
     }
 
@@ -276,16 +560,48 @@ impl OrderBook {
     }
 
     // private helper methods
+
+    /// Register `order` in the lookup tables shared by every resting order, stop orders
+    /// included: `orders` (id -> entry), `user_order_index`, and `client_order_index`. Does
+    /// *not* touch `bids`/`asks`/`stop_buys`/`stop_sells` — callers place the order into
+    /// whichever of those it belongs in themselves.
+    fn index_order(&mut self, order: &Arc<Order>) {
+        let order_entry = OrderEntry {
+            order: order.clone(),
+            price_level_index: 0, // will be updated when needed
+        };
+        self.orders.insert(order.id, order_entry);
+
+        if let Some(user_id) = &order.user_id {
+            self.user_order_index.entry(user_id.clone()).or_default().insert(order.id);
+            if let Some(client_order_id) = order.client_order_id {
+                self.client_order_index.insert((user_id.clone(), client_order_id), order.id);
+            }
+        }
+    }
+
+    /// Inverse of `index_order`: drop `order_id` from `orders`, `user_order_index`, and
+    /// `client_order_index`. Callers are still responsible for removing it from whichever of
+    /// `bids`/`asks`/`stop_buys`/`stop_sells` it was resting in.
+    fn deindex_order(&mut self, order_id: OrderId, user_id: &Option<String>, client_order_id: Option<u64>) {
+        self.orders.remove(&order_id);
+        if let Some(user_id) = user_id {
+            if let Some(ids) = self.user_order_index.get_mut(user_id) {
+                ids.remove(&order_id);
+                if ids.is_empty() {
+                    self.user_order_index.remove(user_id);
+                }
+            }
+            if let Some(client_order_id) = client_order_id {
+                self.client_order_index.remove(&(user_id.clone(), client_order_id));
+            }
+        }
+    }
+
     fn add_to_book_and_match(&mut self, order: &Arc<Order>) -> Result<Vec<Trade>, OrderBookError> {
-        let mut trades = Vec::new();
-        
         // try to match first
-        trades = self.match_orders(order)?;
-        
-        // calculate remaining quantity after trades
-        let total_traded: Quantity = trades.iter().map(|t| t.quantity).sum();
-        let remaining_quantity = order.remaining_quantity - total_traded;
-        
+        let (trades, remaining_quantity) = self.match_orders(order)?;
+
         // if order still has remaining quantity, add to book
         if remaining_quantity > 0 {
             // create a new order with the remaining quantity
@@ -298,12 +614,7 @@ impl OrderBook {
     }
 
     fn add_to_book(&mut self, order: &Arc<Order>) -> Result<(), OrderBookError> {
-        let order_entry = OrderEntry {
-            order: order.clone(),
-            price_level_index: 0, // will be updated when needed
-        };
-
-        self.orders.insert(order.id, order_entry);
+        self.index_order(order);
 
         match order.side {
             Side::Buy => {
@@ -317,13 +628,21 @@ impl OrderBook {
                     .add_order(order.clone());
             }
         }
+        self.emit_level_update(order.side, order.price);
 
         Ok(())
     }
 
-    fn match_orders(&mut self, incoming_order: &Arc<Order>) -> Result<Vec<Trade>, OrderBookError> {
+    /// Matches `incoming_order` against the book. Returns the trades generated plus however much
+    /// of the order's quantity is left afterwards — usually `remaining_quantity - sum(trades)`.
+    /// Self-trade prevention changes that: `DecrementAndCancel` also subtracts the overlap it
+    /// consumed without a trade, and when the incoming order's remainder is cancelled outright
+    /// it is forced to zero so the caller never rests it.
+    fn match_orders(&mut self, incoming_order: &Arc<Order>) -> Result<(Vec<Trade>, Quantity), OrderBookError> {
         let mut trades = Vec::new();
         let mut remaining_quantity = incoming_order.remaining_quantity;
+        let now = chrono::Utc::now();
+        let mut expired_budget = DROP_EXPIRED_ORDER_LIMIT;
 
         match incoming_order.side {
             Side::Buy => {
@@ -334,12 +653,24 @@ impl OrderBook {
                     } else {
                         break;
                     };
-                    
+
                     // for buy orders, match if our price is >= ask price
                     if incoming_order.price >= best_ask_price || incoming_order.order_type == OrderType::Market {
-                        let trade = self.match_at_price_level_ask(best_ask_price, incoming_order, remaining_quantity)?;
-                        remaining_quantity -= trade.quantity;
-                        trades.push(trade);
+                        match self.match_at_price_level_ask(best_ask_price, incoming_order, remaining_quantity, now, &mut expired_budget)? {
+                            MatchStep::Trade(trade) => {
+                                remaining_quantity -= trade.quantity;
+                                trades.push(trade);
+                            }
+                            MatchStep::ExpiredEvicted => continue,
+                            MatchStep::ExpiredBlocked => break,
+                            MatchStep::SelfTrade { consumed, cancel_incoming } => {
+                                remaining_quantity -= consumed;
+                                if cancel_incoming {
+                                    remaining_quantity = 0;
+                                    break;
+                                }
+                            }
+                        }
                     } else {
                         break;
                     }
@@ -353,12 +684,24 @@ impl OrderBook {
                     } else {
                         break;
                     };
-                    
+
                     // for sell orders, match if our price is <= bid price
                     if incoming_order.price <= best_bid_price || incoming_order.order_type == OrderType::Market {
-                        let trade = self.match_at_price_level_bid(best_bid_price, incoming_order, remaining_quantity)?;
-                        remaining_quantity -= trade.quantity;
-                        trades.push(trade);
+                        match self.match_at_price_level_bid(best_bid_price, incoming_order, remaining_quantity, now, &mut expired_budget)? {
+                            MatchStep::Trade(trade) => {
+                                remaining_quantity -= trade.quantity;
+                                trades.push(trade);
+                            }
+                            MatchStep::ExpiredEvicted => continue,
+                            MatchStep::ExpiredBlocked => break,
+                            MatchStep::SelfTrade { consumed, cancel_incoming } => {
+                                remaining_quantity -= consumed;
+                                if cancel_incoming {
+                                    remaining_quantity = 0;
+                                    break;
+                                }
+                            }
+                        }
                     } else {
                         break;
                     }
@@ -366,15 +709,46 @@ impl OrderBook {
             }
         }
 
-        Ok(trades)
+        Ok((trades, remaining_quantity))
     }
 
-    fn match_at_price_level_ask(&mut self, price: Price, incoming_order: &Arc<Order>, max_quantity: Quantity) -> Result<Trade, OrderBookError> {
+    /// Match (or, if the front of the queue has expired, evict) the resting ask at `price`.
+    fn match_at_price_level_ask(
+        &mut self,
+        price: Price,
+        incoming_order: &Arc<Order>,
+        max_quantity: Quantity,
+        now: Timestamp,
+        expired_budget: &mut u32,
+    ) -> Result<MatchStep, OrderBookError> {
         if let Some(price_level) = self.asks.get_mut(&price) {
             if let Some(resting_order) = price_level.orders.front() {
+                if resting_order.is_expired(now) && *expired_budget > 0 {
+                    let evicted = price_level.orders.pop_front().unwrap();
+                    price_level.total_quantity -= evicted.remaining_quantity;
+                    if price_level.orders.is_empty() {
+                        self.asks.remove(&price);
+                    }
+                    self.orders.remove(&evicted.id);
+                    self.stats.expired_orders += 1;
+                    *expired_budget -= 1;
+                    self.emit_level_update(Side::Sell, price);
+                    return Ok(MatchStep::ExpiredEvicted);
+                }
+                if resting_order.is_expired(now) {
+                    // out of eviction budget for this pass; never match against a stale quote
+                    return Ok(MatchStep::ExpiredBlocked);
+                }
+
+                if let Some(behavior) = incoming_order.self_trade_behavior {
+                    if incoming_order.user_id.is_some() && incoming_order.user_id == resting_order.user_id {
+                        return self.apply_self_trade_ask(price, max_quantity, behavior);
+                    }
+                }
+
                 let trade_quantity = max_quantity.min(resting_order.remaining_quantity);
                 let trade_price = resting_order.price; // use resting order's price
-                
+
                 let trade = Trade::new(
                     incoming_order.id,
                     resting_order.id,
@@ -400,8 +774,9 @@ impl OrderBook {
                 if price_level.orders.is_empty() {
                     self.asks.remove(&price);
                 }
+                self.emit_level_update(Side::Sell, price);
 
-                Ok(trade)
+                Ok(MatchStep::Trade(trade))
             } else {
                 Err(OrderBookError::OrderNotFound { order_id: incoming_order.id })
             }
@@ -410,12 +785,87 @@ impl OrderBook {
         }
     }
 
-    fn match_at_price_level_bid(&mut self, price: Price, incoming_order: &Arc<Order>, max_quantity: Quantity) -> Result<Trade, OrderBookError> {
+    /// Apply `behavior` for a detected ask-side self-trade instead of generating a `Trade`.
+    fn apply_self_trade_ask(
+        &mut self,
+        price: Price,
+        max_quantity: Quantity,
+        behavior: SelfTradeBehavior,
+    ) -> Result<MatchStep, OrderBookError> {
+        let price_level = self.asks.get_mut(&price).expect("price level must exist");
+        let resting_order = price_level.orders.front().expect("front order must exist").clone();
+        self.stats.self_trades_suppressed += 1;
+
+        let step = match behavior {
+            SelfTradeBehavior::CancelProvide => {
+                price_level.total_quantity -= resting_order.remaining_quantity;
+                price_level.orders.pop_front();
+                self.orders.remove(&resting_order.id);
+                MatchStep::SelfTrade { consumed: 0, cancel_incoming: false }
+            }
+            SelfTradeBehavior::AbortTransaction => {
+                MatchStep::SelfTrade { consumed: 0, cancel_incoming: true }
+            }
+            SelfTradeBehavior::DecrementAndCancel => {
+                let overlap = max_quantity.min(resting_order.remaining_quantity);
+                price_level.total_quantity -= overlap;
+                if resting_order.remaining_quantity == overlap {
+                    price_level.orders.pop_front();
+                    self.orders.remove(&resting_order.id);
+                } else {
+                    let mut updated_order = resting_order.as_ref().clone();
+                    updated_order.remaining_quantity -= overlap;
+                    price_level.orders.pop_front();
+                    price_level.orders.push_front(Arc::new(updated_order));
+                }
+                MatchStep::SelfTrade { consumed: overlap, cancel_incoming: false }
+            }
+        };
+
+        if price_level.orders.is_empty() {
+            self.asks.remove(&price);
+        }
+        self.emit_level_update(Side::Sell, price);
+        Ok(step)
+    }
+
+    /// Match (or, if the front of the queue has expired, evict) the resting bid at `price`.
+    fn match_at_price_level_bid(
+        &mut self,
+        price: Price,
+        incoming_order: &Arc<Order>,
+        max_quantity: Quantity,
+        now: Timestamp,
+        expired_budget: &mut u32,
+    ) -> Result<MatchStep, OrderBookError> {
         if let Some(price_level) = self.bids.get_mut(&price) {
             if let Some(resting_order) = price_level.orders.front() {
+                if resting_order.is_expired(now) && *expired_budget > 0 {
+                    let evicted = price_level.orders.pop_front().unwrap();
+                    price_level.total_quantity -= evicted.remaining_quantity;
+                    if price_level.orders.is_empty() {
+                        self.bids.remove(&price);
+                    }
+                    self.orders.remove(&evicted.id);
+                    self.stats.expired_orders += 1;
+                    *expired_budget -= 1;
+                    self.emit_level_update(Side::Buy, price);
+                    return Ok(MatchStep::ExpiredEvicted);
+                }
+                if resting_order.is_expired(now) {
+                    // out of eviction budget for this pass; never match against a stale quote
+                    return Ok(MatchStep::ExpiredBlocked);
+                }
+
+                if let Some(behavior) = incoming_order.self_trade_behavior {
+                    if incoming_order.user_id.is_some() && incoming_order.user_id == resting_order.user_id {
+                        return self.apply_self_trade_bid(price, max_quantity, behavior);
+                    }
+                }
+
                 let trade_quantity = max_quantity.min(resting_order.remaining_quantity);
                 let trade_price = resting_order.price; // use resting order's price
-                
+
                 let trade = Trade::new(
                     resting_order.id,
                     incoming_order.id,
@@ -441,8 +891,9 @@ impl OrderBook {
                 if price_level.orders.is_empty() {
                     self.bids.remove(&price);
                 }
+                self.emit_level_update(Side::Buy, price);
 
-                Ok(trade)
+                Ok(MatchStep::Trade(trade))
             } else {
                 Err(OrderBookError::OrderNotFound { order_id: incoming_order.id })
             }
@@ -451,6 +902,50 @@ impl OrderBook {
         }
     }
 
+    /// Apply `behavior` for a detected bid-side self-trade instead of generating a `Trade`.
+    fn apply_self_trade_bid(
+        &mut self,
+        price: Price,
+        max_quantity: Quantity,
+        behavior: SelfTradeBehavior,
+    ) -> Result<MatchStep, OrderBookError> {
+        let price_level = self.bids.get_mut(&price).expect("price level must exist");
+        let resting_order = price_level.orders.front().expect("front order must exist").clone();
+        self.stats.self_trades_suppressed += 1;
+
+        let step = match behavior {
+            SelfTradeBehavior::CancelProvide => {
+                price_level.total_quantity -= resting_order.remaining_quantity;
+                price_level.orders.pop_front();
+                self.orders.remove(&resting_order.id);
+                MatchStep::SelfTrade { consumed: 0, cancel_incoming: false }
+            }
+            SelfTradeBehavior::AbortTransaction => {
+                MatchStep::SelfTrade { consumed: 0, cancel_incoming: true }
+            }
+            SelfTradeBehavior::DecrementAndCancel => {
+                let overlap = max_quantity.min(resting_order.remaining_quantity);
+                price_level.total_quantity -= overlap;
+                if resting_order.remaining_quantity == overlap {
+                    price_level.orders.pop_front();
+                    self.orders.remove(&resting_order.id);
+                } else {
+                    let mut updated_order = resting_order.as_ref().clone();
+                    updated_order.remaining_quantity -= overlap;
+                    price_level.orders.pop_front();
+                    price_level.orders.push_front(Arc::new(updated_order));
+                }
+                MatchStep::SelfTrade { consumed: overlap, cancel_incoming: false }
+            }
+        };
+
+        if price_level.orders.is_empty() {
+            self.bids.remove(&price);
+        }
+        self.emit_level_update(Side::Buy, price);
+        Ok(step)
+    }
+
     fn match_market_order(&mut self, order: &Arc<Order>) -> Result<Vec<Trade>, OrderBookError> {
         // convert market order to aggressive limit order
         let aggressive_price = match order.side {
@@ -462,15 +957,15 @@ impl OrderBook {
         market_order.price = aggressive_price;
         let market_order_arc = Arc::new(market_order);
 
-        self.match_orders(&market_order_arc)
+        Ok(self.match_orders(&market_order_arc)?.0)
     }
 
     fn match_fill_or_kill(&mut self, order: &Arc<Order>) -> Result<Vec<Trade>, OrderBookError> {
         // check if we can fill the entire order
         let available_quantity = self.get_available_quantity_for_order(order);
-        
+
         if available_quantity >= order.quantity {
-            self.match_orders(order)
+            Ok(self.match_orders(order)?.0)
         } else {
             // reject the order
             Ok(Vec::new())
@@ -479,7 +974,126 @@ impl OrderBook {
 
     fn match_immediate_or_cancel(&mut self, order: &Arc<Order>) -> Result<Vec<Trade>, OrderBookError> {
         // match what we can, cancel the rest
-        self.match_orders(order)
+        Ok(self.match_orders(order)?.0)
+    }
+
+    /// Rest a `PostOnly`/`PostOnlySlide` order without ever routing it through `match_orders`.
+    /// `PostOnly` is rejected outright if it would take liquidity; `PostOnlySlide` is instead
+    /// repriced one tick inside the spread so it posts as a maker.
+    fn add_post_only(&mut self, order: &Arc<Order>) -> Result<Vec<Trade>, OrderBookError> {
+        let tick_size = self.market_config.map_or(1, |config| config.tick_size).max(1);
+
+        let would_cross = match order.side {
+            Side::Buy => self.get_best_ask().is_some_and(|best_ask| order.price >= best_ask),
+            Side::Sell => self.get_best_bid().is_some_and(|best_bid| order.price <= best_bid),
+        };
+
+        if !would_cross {
+            self.add_to_book(order)?;
+            return Ok(Vec::new());
+        }
+
+        if order.order_type == OrderType::PostOnly {
+            return Err(OrderBookError::WouldCrossBook { order_id: order.id });
+        }
+
+        let slid_price = match order.side {
+            Side::Buy => {
+                let best_ask = self.get_best_ask().unwrap();
+                order.price.min(best_ask.saturating_sub(tick_size))
+            }
+            Side::Sell => {
+                let best_bid = self.get_best_bid().unwrap();
+                order.price.max(best_bid + tick_size)
+            }
+        };
+
+        let mut slid_order = order.as_ref().clone();
+        slid_order.price = slid_price;
+        self.add_to_book(&Arc::new(slid_order))?;
+        Ok(Vec::new())
+    }
+
+    /// Queue a `Stop`/`StopLimit` order, dormant until the market trades through its trigger.
+    /// Kept out of `self.bids`/`self.asks` entirely since it never participates in matching
+    /// until it fires, but still indexed in `orders`/`user_order_index`/`client_order_index`
+    /// like any other resting order so `cancel_order` and the bulk-cancel helpers can find it.
+    fn add_stop_order(&mut self, order: &Arc<Order>) {
+        let trigger_price = match order.order_type {
+            OrderType::Stop { trigger_price } => trigger_price,
+            OrderType::StopLimit { trigger_price, .. } => trigger_price,
+            _ => unreachable!("add_stop_order called with a non-stop order type"),
+        };
+
+        self.index_order(order);
+
+        let stops = match order.side {
+            Side::Buy => &mut self.stop_buys,
+            Side::Sell => &mut self.stop_sells,
+        };
+        stops.entry(trigger_price).or_default().push_back(order.clone());
+    }
+
+    /// Fire every stop whose trigger the current `last_trade_price` has crossed, converting each
+    /// into a live `Market` (`Stop`) or limit (`StopLimit`) order and feeding it back through
+    /// `add_order`. That call's own trade handling re-arms `last_trade_price` and recurses back
+    /// into this method, so a cascade of fills tripping the next stop is handled automatically.
+    fn check_and_trigger_stops(&mut self) -> Vec<Trade> {
+        let Some(last_price) = self.last_trade_price else {
+            return Vec::new();
+        };
+
+        let mut triggered: Vec<Arc<Order>> = Vec::new();
+
+        // stop-buys fire once the market trades at or above their trigger
+        let buy_triggers: Vec<Price> = self.stop_buys.range(..=last_price).map(|(&p, _)| p).collect();
+        for trigger in buy_triggers {
+            if let Some(queue) = self.stop_buys.remove(&trigger) {
+                triggered.extend(queue);
+            }
+        }
+
+        // stop-sells fire once the market trades at or below their trigger
+        let sell_triggers: Vec<Price> = self.stop_sells.range(last_price..).map(|(&p, _)| p).collect();
+        for trigger in sell_triggers {
+            if let Some(queue) = self.stop_sells.remove(&trigger) {
+                triggered.extend(queue);
+            }
+        }
+
+        // the dormant entries are gone from `stop_buys`/`stop_sells` now; drop them from the
+        // shared indexes too before firing, since `fire_stop_order` mints a fresh `OrderId`
+        for order in &triggered {
+            self.deindex_order(order.id, &order.user_id, order.client_order_id);
+        }
+
+        let mut trades = Vec::new();
+        for order in triggered {
+            let fired = Self::fire_stop_order(&order);
+            if let Ok(new_trades) = self.add_order(fired) {
+                trades.extend(new_trades);
+            }
+        }
+        trades
+    }
+
+    /// Convert a triggered `Stop` into a `Market` order, or a `StopLimit` into a limit order
+    /// resting at its `limit_price`.
+    fn fire_stop_order(order: &Arc<Order>) -> Order {
+        let mut fired = order.as_ref().clone();
+        fired.id = Uuid::new_v4();
+        fired.timestamp = chrono::Utc::now();
+        match order.order_type {
+            OrderType::Stop { .. } => {
+                fired.order_type = OrderType::Market;
+            }
+            OrderType::StopLimit { limit_price, .. } => {
+                fired.order_type = OrderType::GoodTillCancel;
+                fired.price = limit_price;
+            }
+            _ => unreachable!("fire_stop_order called with a non-stop order type"),
+        }
+        fired
     }
 
     fn get_available_quantity_for_order(&self, order: &Arc<Order>) -> Quantity {
@@ -499,6 +1113,118 @@ impl OrderBook {
         }
     }
 
+    /// Cancel every resting order belonging to `user_id` whose `client_order_id` is in `ids`, in
+    /// one pass, via the `(user_id, client_order_id)` index — this is the bulk "cancel by client
+    /// ids" instruction real exchanges expose, letting a market maker atomically pull a whole
+    /// quote set without round-tripping each internal `OrderId`.
+    pub fn cancel_orders_by_client_ids(&mut self, user_id: &str, client_order_ids: &[u64]) -> Vec<OrderId> {
+        let matching: Vec<OrderId> = client_order_ids
+            .iter()
+            .filter_map(|client_order_id| {
+                self.client_order_index.get(&(user_id.to_string(), *client_order_id)).copied()
+            })
+            .collect();
+
+        for order_id in &matching {
+            let _ = self.cancel_order(*order_id);
+        }
+
+        matching
+    }
+
+    /// Cancel all of a single user's resting orders, regardless of price or side.
+    pub fn cancel_all_orders(&mut self, user_id: &str) -> Vec<OrderId> {
+        let matching: Vec<OrderId> = self
+            .user_order_index
+            .get(user_id)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default();
+
+        for order_id in &matching {
+            let _ = self.cancel_order(*order_id);
+        }
+
+        matching
+    }
+
+    /// Periodic sweep that reaps every resting order past its time-in-force, regardless of the
+    /// per-matching-pass `DROP_EXPIRED_ORDER_LIMIT`: any order past its `expires_at` (GoodTillTime
+    /// / max_ts), plus any `GoodForDay` order that has crossed the configured day-reset boundary.
+    /// Intended to be called on a timer so stale liquidity a matching pass never walked over
+    /// still gets cleaned up, without a full book rebuild.
+    pub fn expire_orders(&mut self, now: Timestamp) -> Vec<OrderId> {
+        let expired_ids: Vec<OrderId> = self
+            .orders
+            .values()
+            .filter(|entry| entry.order.is_expired(now) || self.is_past_day_reset(&entry.order, now))
+            .map(|entry| entry.order.id)
+            .collect();
+
+        for order_id in &expired_ids {
+            let _ = self.cancel_order(*order_id);
+        }
+
+        self.stats.expired_orders += expired_ids.len() as u64;
+        expired_ids
+    }
+
+    /// Whether a `GoodForDay` order was resting across the configured day-reset boundary
+    /// (`day_reset_hour`:`day_reset_minute`) and should therefore be reaped.
+    fn is_past_day_reset(&self, order: &Order, now: Timestamp) -> bool {
+        if order.order_type != OrderType::GoodForDay {
+            return false;
+        }
+
+        let reset_time = chrono::NaiveTime::from_hms_opt(self.day_reset_hour as u32, self.day_reset_minute as u32, 0)
+            .unwrap_or_else(|| chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        let order_date = order.timestamp.date_naive();
+        let boundary_date = if order.timestamp.time() < reset_time {
+            order_date
+        } else {
+            order_date + chrono::Duration::days(1)
+        };
+        let boundary = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(boundary_date.and_time(reset_time), chrono::Utc);
+
+        now >= boundary
+    }
+
+    /// If `now` has crossed the configured day-reset boundary since `last_day_reset`, cancel
+    /// every `GoodForDay` order still resting in the book, advance `last_day_reset` to `now`, and
+    /// return the cancelled ids so callers can notify their owners. A no-op (empty result) if the
+    /// boundary hasn't been crossed yet. Complements the per-order lazy check in
+    /// `is_past_day_reset` with an explicit book-wide sweep a caller can drive on a timer.
+    pub fn process_day_reset(&mut self, now: Timestamp) -> Vec<OrderId> {
+        let reset_time = chrono::NaiveTime::from_hms_opt(self.day_reset_hour as u32, self.day_reset_minute as u32, 0)
+            .unwrap_or_else(|| chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+
+        let last_date = self.last_day_reset.date_naive();
+        let boundary_date = if self.last_day_reset.time() < reset_time {
+            last_date
+        } else {
+            last_date + chrono::Duration::days(1)
+        };
+        let boundary = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(boundary_date.and_time(reset_time), chrono::Utc);
+
+        if now < boundary {
+            return Vec::new();
+        }
+
+        let gfd_ids: Vec<OrderId> = self
+            .orders
+            .values()
+            .filter(|entry| entry.order.order_type == OrderType::GoodForDay)
+            .map(|entry| entry.order.id)
+            .collect();
+
+        for order_id in &gfd_ids {
+            let _ = self.cancel_order(*order_id);
+        }
+
+        self.stats.expired_orders += gfd_ids.len() as u64;
+        self.last_day_reset = now;
+        gfd_ids
+    }
+
     /// Clear all orders from the order book (useful for rebuilding with market data)
     pub fn clear_all_orders(&mut self) {
         self.bids.clear();
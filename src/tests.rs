@@ -229,6 +229,462 @@ mod tests {
         assert_eq!(snapshot.bids[0].quantity, 500);
     }
 
+    #[test]
+    fn test_stop_order_fires_as_market_order_on_trigger() {
+        let mut order_book = OrderBook::new();
+
+        // resting liquidity the stop will hit once it fires
+        let sell_order = Order::new(Side::Sell, OrderType::Limit, 100_000, 1000, None);
+        order_book.add_order(sell_order).unwrap();
+
+        // a buy-stop armed at 99_000 shouldn't do anything yet
+        let stop_order = Order::new(
+            Side::Buy,
+            OrderType::Stop { trigger_price: 99_000 },
+            0,
+            500,
+            Some("user1".to_string()),
+        );
+        let trades = order_book.add_order(stop_order).unwrap();
+        assert_eq!(trades.len(), 0);
+
+        // a trade at 100_000 crosses the 99_000 trigger, so the next order should cascade into
+        // firing the stop as a market buy against the resting sell
+        let trigger_trade = Order::new(Side::Buy, OrderType::Market, 0, 200, None);
+        let trades = order_book.add_order(trigger_trade).unwrap();
+
+        // the triggering trade itself, plus the cascaded stop's fill
+        assert_eq!(trades.len(), 2);
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.asks[0].quantity, 300); // 1000 - 200 - 500
+    }
+
+    #[test]
+    fn test_cancel_order_removes_dormant_stop_order() {
+        let mut order_book = OrderBook::new();
+
+        let stop_order = Order::new(
+            Side::Buy,
+            OrderType::Stop { trigger_price: 99_000 },
+            0,
+            500,
+            Some("user1".to_string()),
+        );
+        let order_id = stop_order.id;
+        order_book.add_order(stop_order).unwrap();
+        assert_eq!(order_book.size(), 1);
+
+        order_book.cancel_order(order_id).unwrap();
+        assert_eq!(order_book.size(), 0);
+
+        // a trade crossing the old trigger must not fire a stop that was already cancelled
+        let sell_order = Order::new(Side::Sell, OrderType::Limit, 100_000, 1000, None);
+        order_book.add_order(sell_order).unwrap();
+        let trigger_trade = Order::new(Side::Buy, OrderType::Market, 0, 200, None);
+        let trades = order_book.add_order(trigger_trade).unwrap();
+        assert_eq!(trades.len(), 1); // just the triggering trade, no cascaded stop fill
+    }
+
+    #[test]
+    fn test_drain_level_deltas_returns_queued_changes() {
+        let mut order_book = OrderBook::new();
+
+        let order = Order::new(Side::Buy, OrderType::Limit, 100_000, 1000, None);
+        order_book.add_order(order).unwrap();
+
+        let deltas = order_book.drain_level_deltas();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].side, Side::Buy);
+        assert_eq!(deltas[0].price, 100_000);
+        assert_eq!(deltas[0].new_quantity, 1000);
+
+        // already drained - nothing new until the book changes again
+        assert!(order_book.drain_level_deltas().is_empty());
+    }
+
+    #[test]
+    fn test_process_day_reset_cancels_good_for_day_orders() {
+        let mut order_book = OrderBook::new();
+        order_book.set_day_reset_time(16, 0);
+
+        let gfd_order = Order::new(
+            Side::Buy,
+            OrderType::GoodForDay,
+            100_000,
+            1000,
+            Some("user1".to_string()),
+        );
+        let gtc_order = Order::new(
+            Side::Sell,
+            OrderType::GoodTillCancel,
+            101_000,
+            1000,
+            Some("user2".to_string()),
+        );
+        order_book.add_order(gfd_order).unwrap();
+        order_book.add_order(gtc_order).unwrap();
+
+        // before the boundary: nothing happens
+        let now = order_book.get_order_book_snapshot(1).timestamp;
+        let cancelled = order_book.process_day_reset(now);
+        assert!(cancelled.is_empty());
+
+        // well past the boundary: the GFD order is reaped, the GTC order is untouched
+        let later = now + chrono::Duration::days(2);
+        let cancelled = order_book.process_day_reset(later);
+        assert_eq!(cancelled.len(), 1);
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.bids.len(), 0);
+        assert_eq!(snapshot.asks.len(), 1);
+    }
+
+    #[test]
+    fn test_modify_order_rejects_invalid_size_without_cancelling_original() {
+        let mut order_book = OrderBook::new();
+        order_book.set_market_config(MarketConfig {
+            tick_size: 1_000,
+            lot_size: 100,
+            min_size: 100,
+        });
+
+        let order = Order::new(
+            Side::Buy,
+            OrderType::Limit,
+            100_000,
+            1000,
+            Some("user1".to_string()),
+        );
+        let order_id = order.id;
+        order_book.add_order(order).unwrap();
+
+        // not a multiple of the lot size - modification must be rejected
+        let result = order_book.modify_order(order_id, None, Some(150));
+        assert!(matches!(result, Err(OrderBookError::InvalidLotSize { .. })));
+
+        // the original order is still resting, untouched
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.bids[0].quantity, 1000);
+    }
+
+    #[test]
+    fn test_post_only_rejected_when_crossing() {
+        let mut order_book = OrderBook::new();
+
+        let sell_order = Order::new(
+            Side::Sell,
+            OrderType::Limit,
+            100_000,
+            500,
+            Some("user1".to_string()),
+        );
+        order_book.add_order(sell_order).unwrap();
+
+        // a post-only buy at or above the best ask would take liquidity - must be rejected
+        let post_only = Order::new(
+            Side::Buy,
+            OrderType::PostOnly,
+            100_000,
+            200,
+            Some("user2".to_string()),
+        );
+
+        let result = order_book.add_order(post_only);
+        assert!(matches!(result, Err(OrderBookError::WouldCrossBook { .. })));
+
+        // the resting sell order is untouched
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.asks.len(), 1);
+        assert_eq!(snapshot.asks[0].quantity, 500);
+    }
+
+    #[test]
+    fn test_post_only_slide_reprices_inside_spread() {
+        let mut order_book = OrderBook::new();
+        order_book.set_market_config(MarketConfig {
+            tick_size: 100,
+            lot_size: 1,
+            min_size: 1,
+        });
+
+        let sell_order = Order::new(
+            Side::Sell,
+            OrderType::Limit,
+            100_000,
+            500,
+            Some("user1".to_string()),
+        );
+        order_book.add_order(sell_order).unwrap();
+
+        // would cross at 100_000, so it should slide to one tick inside the ask
+        let slide_order = Order::new(
+            Side::Buy,
+            OrderType::PostOnlySlide,
+            100_000,
+            200,
+            Some("user2".to_string()),
+        );
+
+        let trades = order_book.add_order(slide_order).unwrap();
+        assert_eq!(trades.len(), 0); // never takes liquidity
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.bids[0].price, 99_900); // one tick inside the ask
+        assert_eq!(snapshot.asks[0].quantity, 500); // resting sell untouched
+    }
+
+    #[test]
+    fn test_self_trade_decrement_and_cancel_reduces_both_sides() {
+        let mut order_book = OrderBook::new();
+
+        let resting_sell = Order::new(
+            Side::Sell,
+            OrderType::Limit,
+            100_000,
+            500,
+            Some("trader1".to_string()),
+        );
+        order_book.add_order(resting_sell).unwrap();
+
+        let incoming_buy = Order::new(
+            Side::Buy,
+            OrderType::Limit,
+            100_000,
+            300,
+            Some("trader1".to_string()),
+        )
+        .with_self_trade_behavior(SelfTradeBehavior::DecrementAndCancel);
+
+        let trades = order_book.add_order(incoming_buy).unwrap();
+        assert_eq!(trades.len(), 0); // self-trades are suppressed, never produce a Trade
+        assert_eq!(order_book.get_market_data_stats().self_trades_suppressed, 1);
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.bids.len(), 0); // incoming fully consumed, nothing left to rest
+        assert_eq!(snapshot.asks[0].quantity, 200); // resting reduced by the overlap
+    }
+
+    #[test]
+    fn test_self_trade_cancel_provide_removes_resting_order_and_fills_nothing() {
+        let mut order_book = OrderBook::new();
+
+        let resting_sell = Order::new(
+            Side::Sell,
+            OrderType::Limit,
+            100_000,
+            500,
+            Some("trader1".to_string()),
+        );
+        order_book.add_order(resting_sell).unwrap();
+
+        let incoming_buy = Order::new(
+            Side::Buy,
+            OrderType::Limit,
+            100_000,
+            300,
+            Some("trader1".to_string()),
+        )
+        .with_self_trade_behavior(SelfTradeBehavior::CancelProvide);
+
+        let trades = order_book.add_order(incoming_buy).unwrap();
+        assert_eq!(trades.len(), 0);
+        assert_eq!(order_book.get_market_data_stats().self_trades_suppressed, 1);
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.asks.len(), 0); // the resting order was cancelled, not traded against
+        assert_eq!(snapshot.bids[0].quantity, 300); // incoming rests unchanged, nothing consumed
+    }
+
+    #[test]
+    fn test_self_trade_abort_transaction_discards_the_incoming_order_entirely() {
+        let mut order_book = OrderBook::new();
+
+        let resting_sell = Order::new(
+            Side::Sell,
+            OrderType::Limit,
+            100_000,
+            500,
+            Some("trader1".to_string()),
+        );
+        order_book.add_order(resting_sell).unwrap();
+
+        let incoming_buy = Order::new(
+            Side::Buy,
+            OrderType::Limit,
+            100_000,
+            300,
+            Some("trader1".to_string()),
+        )
+        .with_self_trade_behavior(SelfTradeBehavior::AbortTransaction);
+
+        let trades = order_book.add_order(incoming_buy).unwrap();
+        assert_eq!(trades.len(), 0);
+        assert_eq!(order_book.get_market_data_stats().self_trades_suppressed, 1);
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.bids.len(), 0); // forced to remaining_quantity 0, never rested
+        assert_eq!(snapshot.asks[0].quantity, 500); // resting order untouched
+    }
+
+    #[test]
+    fn test_order_with_expiry_in_the_past_is_rejected_on_entry() {
+        let mut order_book = OrderBook::new();
+        let expired_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+
+        let order = Order::new(Side::Buy, OrderType::Limit, 100_000, 500, Some("user1".to_string()))
+            .with_expiry(expired_at);
+
+        let result = order_book.add_order(order);
+        assert!(matches!(result, Err(OrderBookError::OrderExpired { .. })));
+        assert_eq!(order_book.size(), 0);
+    }
+
+    #[test]
+    fn test_expire_orders_sweeps_gtt_orders_past_their_deadline() {
+        let mut order_book = OrderBook::new();
+        let now = chrono::Utc::now();
+
+        // rests fine now, but its deadline passes before the sweep runs
+        let soon_to_expire = Order::new(Side::Buy, OrderType::Limit, 100_000, 500, Some("user1".to_string()))
+            .with_expiry(now + chrono::Duration::seconds(1));
+        let kept_order = Order::new(Side::Sell, OrderType::Limit, 101_000, 500, Some("user2".to_string()));
+
+        order_book.add_order(soon_to_expire).unwrap();
+        order_book.add_order(kept_order).unwrap();
+        assert_eq!(order_book.size(), 2);
+
+        let expired_ids = order_book.expire_orders(now + chrono::Duration::seconds(2));
+        assert_eq!(expired_ids.len(), 1);
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.bids.len(), 0); // the GTT order was swept
+        assert_eq!(snapshot.asks.len(), 1); // the order without a deadline survives
+        assert_eq!(order_book.get_market_data_stats().expired_orders, 1);
+    }
+
+    #[test]
+    fn test_oracle_peg_order_rejected_before_oracle_price_is_set() {
+        let mut order_book = OrderBook::new();
+
+        let peg_order = Order::new(
+            Side::Sell,
+            OrderType::OraclePeg { offset: 0, limit: None },
+            0,
+            500,
+            Some("user1".to_string()),
+        );
+        let result = order_book.add_order(peg_order);
+        assert!(matches!(result, Err(OrderBookError::OraclePriceUnknown { .. })));
+        assert_eq!(order_book.size(), 0);
+    }
+
+    #[test]
+    fn test_oracle_peg_order_clamps_to_its_limit() {
+        let mut order_book = OrderBook::new();
+        order_book.set_oracle_price(100_000);
+
+        // a sell pegged 5_000 below the oracle would rest at 95_000, but its limit says it must
+        // never rest below 98_000
+        let peg_order = Order::new(
+            Side::Sell,
+            OrderType::OraclePeg { offset: -5_000, limit: Some(98_000) },
+            0,
+            500,
+            Some("user1".to_string()),
+        );
+        order_book.add_order(peg_order).unwrap();
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.asks[0].price, 98_000);
+    }
+
+    #[test]
+    fn test_oracle_peg_repricing_preserves_fifo_order_within_a_shared_offset() {
+        let mut order_book = OrderBook::new();
+        order_book.set_oracle_price(100_000);
+
+        // same offset, so both land on the same level once repriced; `a` rests first
+        let peg_a = Order::new(
+            Side::Sell,
+            OrderType::OraclePeg { offset: 1_000, limit: None },
+            0,
+            300,
+            Some("a".to_string()),
+        );
+        order_book.add_order(peg_a).unwrap();
+        let peg_b = Order::new(
+            Side::Sell,
+            OrderType::OraclePeg { offset: 1_000, limit: None },
+            0,
+            500,
+            Some("b".to_string()),
+        );
+        order_book.add_order(peg_b).unwrap();
+
+        // both now sit at 101_000; moving the oracle down merges them onto 100_000
+        order_book.set_oracle_price(99_000);
+
+        // a market buy for exactly `a`'s original quantity should fully consume `a` and leave
+        // `b` untouched if FIFO priority survived the reprice - if it didn't, `b` would have
+        // partially filled instead and `a` would still be sitting in the level
+        let buy_order = Order::new(Side::Buy, OrderType::Market, 0, 300, None);
+        order_book.add_order(buy_order).unwrap();
+
+        let snapshot = order_book.get_order_book_snapshot(5);
+        assert_eq!(snapshot.asks[0].price, 100_000);
+        assert_eq!(snapshot.asks[0].order_count, 1);
+        assert_eq!(snapshot.asks[0].quantity, 500);
+    }
+
+    #[test]
+    fn test_candle_aggregator_rolls_trades_into_buckets_and_closes_on_rollover() {
+        let mut aggregator = CandleAggregator::new(CandleInterval::OneSecond, 4);
+        let bucket_one = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        let trade_open = Trade {
+            id: Uuid::new_v4(),
+            buy_order_id: Uuid::new_v4(),
+            sell_order_id: Uuid::new_v4(),
+            price: 100_000,
+            quantity: 10,
+            timestamp: bucket_one,
+        };
+        aggregator.push_trade(&trade_open);
+
+        let trade_high = Trade {
+            price: 100_500,
+            quantity: 5,
+            timestamp: bucket_one,
+            ..trade_open.clone()
+        };
+        aggregator.push_trade(&trade_high);
+
+        let partial = aggregator.current_partial().unwrap();
+        assert_eq!(partial.open, 100_000);
+        assert_eq!(partial.high, 100_500);
+        assert_eq!(partial.low, 100_000);
+        assert_eq!(partial.close, 100_500);
+        assert_eq!(partial.volume, 15);
+        assert_eq!(aggregator.closed_candles().count(), 0);
+
+        // a trade one second later rolls over into a new bucket, closing the first candle
+        let trade_next_bucket = Trade {
+            price: 99_500,
+            quantity: 3,
+            timestamp: bucket_one + chrono::Duration::seconds(1),
+            ..trade_open
+        };
+        aggregator.push_trade(&trade_next_bucket);
+
+        assert_eq!(aggregator.closed_candles().count(), 1);
+        let closed = aggregator.last_closed().unwrap();
+        assert_eq!(closed.close, 100_500);
+        assert_eq!(aggregator.current_partial().unwrap().open, 99_500);
+    }
+
     #[tokio::test]
     async fn test_market_data_processing() {
         let order_book = Arc::new(Mutex::new(OrderBook::new()));
@@ -243,6 +699,7 @@ mod tests {
             quantity: 1000,
             timestamp: chrono::Utc::now(),
             sequence_number: 1,
+            client_order_id: None,
         });
         
         let result = processor.process_market_data(message).await;
@@ -252,4 +709,111 @@ mod tests {
         assert_eq!(stats.messages_processed, 1);
         assert_eq!(stats.new_orders, 1);
     }
+
+    fn new_order_message(sequence_number: u64, price: Price) -> MarketDataMessage {
+        MarketDataMessage::NewOrder(NewOrderMessage {
+            message_type: MessageType::NewOrder,
+            order_id: Uuid::new_v4(),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            price,
+            quantity: 100,
+            timestamp: chrono::Utc::now(),
+            sequence_number,
+            client_order_id: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_sequential_new_orders_dont_spuriously_gap_on_level_revisions() {
+        let order_book = Arc::new(Mutex::new(OrderBook::new()));
+        let mut processor = MarketDataProcessor::new(order_book.clone());
+
+        // each message rests at its own price, so each bumps `book_revision` via
+        // `emit_level_update` - that must not be confused with the feed's own sequence_number
+        let messages = [
+            new_order_message(1, 100_000),
+            new_order_message(2, 99_000),
+            new_order_message(3, 98_000),
+        ];
+        for message in messages {
+            let result = processor.process_market_data(message).await;
+            assert!(result.is_ok(), "unexpectedly failed: {result:?}");
+        }
+
+        assert_eq!(processor.get_stats().messages_processed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_atomic_batch_of_valid_sequential_orders_all_apply() {
+        let order_book = Arc::new(Mutex::new(OrderBook::new()));
+        let mut processor = MarketDataProcessor::new(order_book.clone());
+
+        let messages = vec![
+            new_order_message(1, 100_000),
+            new_order_message(2, 99_000),
+            new_order_message(3, 98_000),
+        ];
+
+        let result = processor.process_market_data_batch_atomic(messages).await;
+        assert!(matches!(result, Ok(3)), "unexpected result: {result:?}");
+    }
+
+    #[tokio::test]
+    async fn test_atomic_batch_rolls_back_and_forces_a_revision_gap_for_subscribers() {
+        let order_book = Arc::new(Mutex::new(OrderBook::new()));
+        let mut processor = MarketDataProcessor::new(order_book.clone());
+
+        // seed one resting order before the batch so we can confirm it survives the rollback
+        processor.process_market_data(new_order_message(1, 100_000)).await.unwrap();
+
+        let mut receiver = order_book.lock().unwrap().subscribe(5).1;
+
+        // first message is valid and rests at a new level (broadcasting a LevelUpdate); the
+        // second repeats sequence_number 2, which is <= last_sequence_number and must be rejected
+        let messages = vec![new_order_message(2, 99_000), new_order_message(2, 98_000)];
+        let result = processor.process_market_data_batch_atomic(messages).await;
+        assert!(matches!(result, Err((1, OrderBookError::SequenceGap { .. }))));
+
+        // the first (valid) message in the failed batch must be rolled back too
+        let snapshot = order_book.lock().unwrap().get_order_book_snapshot(5);
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.bids[0].price, 100_000);
+
+        // drive one more valid message past the rollback so something broadcasts again
+        processor.process_market_data(new_order_message(2, 97_000)).await.unwrap();
+
+        let first_seen = receiver.recv().await.unwrap().sequence_number;
+        let second_seen = receiver.recv().await.unwrap().sequence_number;
+        assert!(second_seen > first_seen + 1, "expected a forced gap, got {first_seen} -> {second_seen}");
+    }
+
+    #[test]
+    fn test_matching_continues_past_an_exhausted_eviction_budget_when_progress_was_made() {
+        let mut order_book = OrderBook::new();
+        // `add_order` rejects orders that are already expired, so these get a deadline just
+        // ahead of now that has passed by the time the buy arrives
+        let expires_at = chrono::Utc::now() + chrono::Duration::milliseconds(50);
+
+        // stack exactly DROP_EXPIRED_ORDER_LIMIT (5) expired asks at the front of the queue,
+        // followed by one live, matchable ask at the same price. Evicting the 5th expired order
+        // exhausts the per-pass budget on a step that still made progress, so the live order
+        // right behind it must still get matched in this same pass rather than the matching loop
+        // breaking early.
+        for _ in 0..5 {
+            let expiring_ask = Order::new(Side::Sell, OrderType::Limit, 100_000, 100, Some("stale".to_string()))
+                .with_expiry(expires_at);
+            order_book.add_order(expiring_ask).unwrap();
+        }
+        let live_ask = Order::new(Side::Sell, OrderType::Limit, 100_000, 100, Some("user2".to_string()));
+        order_book.add_order(live_ask).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let buy_order = Order::new(Side::Buy, OrderType::Limit, 100_000, 100, Some("user1".to_string()));
+        let trades = order_book.add_order(buy_order).unwrap();
+
+        assert_eq!(trades.len(), 1, "expected the incoming order to trade against the live ask");
+        assert_eq!(trades[0].quantity, 100);
+        assert_eq!(order_book.get_market_data_stats().expired_orders, 5);
+    }
 }
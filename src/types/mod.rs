@@ -12,6 +12,17 @@ pub enum Side {
     Sell,
 }
 
+/// How to resolve a match between two orders from the same `user_id` (self-trade prevention).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SelfTradeBehavior {
+    /// Remove the resting order and keep matching deeper into the book.
+    CancelProvide,
+    /// Stop filling the aggressor and cancel its unfilled remainder.
+    AbortTransaction,
+    /// Reduce both orders by the overlap quantity; whichever is smaller ends up cancelled.
+    DecrementAndCancel,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OrderType {
     Limit,
@@ -20,6 +31,21 @@ pub enum OrderType {
     FillOrKill,        // fok
     GoodTillCancel,    // gtc
     GoodForDay,        // gfd
+    GoodTillTime,      // gtt, expires at `Order::expires_at`
+    PostOnly,          // reject outright rather than take liquidity
+    PostOnlySlide,     // reprice to just inside the spread rather than take liquidity
+    /// Effective price floats with the oracle reference: `oracle_price + offset`, clamped to
+    /// never trade through `limit` if set. `offset` is negative for bids that want to sit below
+    /// the oracle.
+    OraclePeg { offset: i64, limit: Option<Price> },
+    /// Dormant until the market trades through `trigger_price`, then fires as a `Market` order.
+    Stop { trigger_price: Price },
+    /// Dormant until the market trades through `trigger_price`, then fires as a limit order
+    /// resting at `limit_price`.
+    StopLimit {
+        trigger_price: Price,
+        limit_price: Price,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +58,14 @@ pub struct Order {
     pub remaining_quantity: Quantity,
     pub timestamp: Timestamp,
     pub user_id: Option<String>,
+    /// Wall-clock deadline past which this order must not rest or trade (GoodTillTime / max_ts).
+    pub expires_at: Option<Timestamp>,
+    /// Caller-chosen id, unique per `user_id`, used for bulk cancellation without tracking
+    /// internal `OrderId`s.
+    pub client_order_id: Option<u64>,
+    /// How to resolve a match against a resting order with the same `user_id`. `None` means no
+    /// self-trade prevention: the order is free to cross its own resting orders.
+    pub self_trade_behavior: Option<SelfTradeBehavior>,
 }
 
 impl Order {
@@ -51,9 +85,32 @@ impl Order {
             remaining_quantity: quantity,
             timestamp: chrono::Utc::now(),
             user_id,
+            expires_at: None,
+            client_order_id: None,
+            self_trade_behavior: None,
         }
     }
 
+    /// Attach a GoodTillTime deadline to this order (max_ts). Matching must refuse to rest or
+    /// fill it once `expires_at` has passed.
+    pub fn with_expiry(mut self, expires_at: Timestamp) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Attach a caller-chosen id (unique per `user_id`) so the order can later be bulk-cancelled
+    /// without the caller having to track the internal `OrderId`.
+    pub fn with_client_order_id(mut self, client_order_id: u64) -> Self {
+        self.client_order_id = Some(client_order_id);
+        self
+    }
+
+    /// Opt this order into self-trade prevention against resting orders with the same `user_id`.
+    pub fn with_self_trade_behavior(mut self, behavior: SelfTradeBehavior) -> Self {
+        self.self_trade_behavior = Some(behavior);
+        self
+    }
+
     pub fn is_filled(&self) -> bool {
         self.remaining_quantity == 0
     }
@@ -61,6 +118,10 @@ impl Order {
     pub fn is_active(&self) -> bool {
         !self.is_filled() && self.order_type != OrderType::FillOrKill
     }
+
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.expires_at.is_some_and(|deadline| now >= deadline)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,6 +152,14 @@ impl Trade {
     }
 }
 
+/// Per-symbol venue constraints, mirroring the tick/lot/min-size hygiene real exchanges enforce.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MarketConfig {
+    pub tick_size: Price,
+    pub lot_size: Quantity,
+    pub min_size: Quantity,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LevelInfo {
     pub price: Price,
@@ -106,6 +175,37 @@ pub struct OrderBookSnapshot {
     pub sequence_number: u64,
 }
 
+/// A single price level's aggregate changed. `new_quantity == 0` signals the level was removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelUpdate {
+    pub side: Side,
+    pub price: Price,
+    pub new_quantity: Quantity,
+    pub order_count: usize,
+    pub sequence_number: u64,
+}
+
+/// A single price-level aggregate change, queued for polling rather than pushed to a broadcast
+/// subscriber. `new_quantity == 0` means the level was removed. Pairs with
+/// `OrderBook::drain_level_deltas`/`checkpoint`: bootstrap from a checkpoint, then apply drained
+/// deltas in order, detecting gaps via `sequence_number`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelDelta {
+    pub side: Side,
+    pub price: Price,
+    pub new_quantity: Quantity,
+    pub sequence_number: u64,
+}
+
+/// A consistent full snapshot a subscriber can bootstrap from before applying `LevelUpdate`s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookCheckpoint {
+    pub bids: Vec<LevelInfo>,
+    pub asks: Vec<LevelInfo>,
+    pub sequence_number: u64,
+    pub timestamp: Timestamp,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageType {
     NewOrder,
@@ -113,6 +213,8 @@ pub enum MessageType {
     ModifyOrder,
     Trade,
     BookSnapshot,
+    LevelUpdate,
+    BookCheckpoint,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -125,6 +227,7 @@ pub struct NewOrderMessage {
     pub quantity: Quantity,
     pub timestamp: Timestamp,
     pub sequence_number: u64,
+    pub client_order_id: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -165,6 +268,25 @@ pub struct BookSnapshotMessage {
     pub sequence_number: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelUpdateMessage {
+    pub message_type: MessageType,
+    pub side: Side,
+    pub price: Price,
+    pub new_quantity: Quantity,
+    pub timestamp: Timestamp,
+    pub sequence_number: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookCheckpointMessage {
+    pub message_type: MessageType,
+    pub bids: Vec<LevelInfo>,
+    pub asks: Vec<LevelInfo>,
+    pub timestamp: Timestamp,
+    pub sequence_number: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MarketDataMessage {
     NewOrder(NewOrderMessage),
@@ -172,6 +294,12 @@ pub enum MarketDataMessage {
     ModifyOrder(ModifyOrderMessage),
     Trade(TradeMessage),
     BookSnapshot(BookSnapshotMessage),
+    /// A single price-level aggregate changed (`new_quantity == 0` means the level was removed).
+    /// Consumers apply these in order after bootstrapping from a `BookCheckpoint`.
+    LevelUpdate(LevelUpdateMessage),
+    /// A full aggregated snapshot a consumer can initialize a replica from before applying
+    /// subsequent `LevelUpdate`s.
+    BookCheckpoint(BookCheckpointMessage),
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -184,6 +312,9 @@ pub struct MarketDataStats {
     pub snapshots: u64,
     pub errors: u64,
     pub sequence_gaps: u64,
+    pub expired_orders: u64,
+    pub level_updates: u64,
+    pub self_trades_suppressed: u64,
     pub total_processing_time: std::time::Duration,
     pub max_latency: std::time::Duration,
     pub min_latency: std::time::Duration,
@@ -216,10 +347,28 @@ pub enum OrderBookError {
     
     #[error("Order already exists: {order_id}")]
     OrderAlreadyExists { order_id: OrderId },
-    
+
+    #[error("Order expired: {order_id}")]
+    OrderExpired { order_id: OrderId },
+
+    #[error("Price {price} is not a multiple of the configured tick size")]
+    InvalidTickSize { price: Price },
+
+    #[error("Quantity {quantity} is not a multiple of the configured lot size")]
+    InvalidLotSize { quantity: Quantity },
+
+    #[error("Quantity {quantity} is below the configured minimum order size")]
+    BelowMinimumSize { quantity: Quantity },
+
+    #[error("PostOnly order {order_id} would have crossed the book")]
+    WouldCrossBook { order_id: OrderId },
+
     #[error("Sequence gap detected: expected {expected}, got {actual}")]
     SequenceGap { expected: u64, actual: u64 },
     
     #[error("Market data error: {message}")]
     MarketDataError { message: String },
+
+    #[error("OraclePeg order {order_id} rejected: no oracle price has been set yet")]
+    OraclePriceUnknown { order_id: OrderId },
 }